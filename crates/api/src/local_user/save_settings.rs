@@ -127,8 +127,12 @@ impl Perform for SaveUserSettings {
         Some(discussion_languages)
       };
 
-      blocking(context.pool(), move |conn| {
-        LocalUserLanguage::update_user_languages(conn, languages, local_user_id)
+      blocking(context.pool(), move |conn| -> Result<(), LemmyError> {
+        let languages = languages
+          .map(|l| LocalUserLanguage::sanitize_input(conn, l))
+          .transpose()?;
+        LocalUserLanguage::update_user_languages(conn, languages, local_user_id)?;
+        Ok(())
       })
       .await??;
     }
@@ -60,7 +60,7 @@ impl Perform for LeaveAdmin {
 
     let federated_instances = build_federated_instances(context.pool(), context.settings()).await?;
 
-    let all_languages = blocking(context.pool(), Language::read_all).await??;
+    let all_languages = blocking(context.pool(), |conn| Language::read_all(conn, true)).await??;
 
     Ok(GetSiteResponse {
       site_view: Some(site_view),
@@ -91,12 +91,9 @@ impl PerformCrud for CreatePost {
       .map(|u| (Some(u.title), Some(u.description), Some(u.embed_video_url)))
       .unwrap_or_default();
     let language_id = Some(
-      data.language_id.unwrap_or(
-        blocking(context.pool(), move |conn| {
-          Language::read_undetermined(conn)
-        })
-        .await??,
-      ),
+      data
+        .language_id
+        .unwrap_or(blocking(context.pool(), Language::undetermined_id).await??),
     );
 
     let post_form = PostForm {
@@ -30,10 +30,10 @@ impl PerformCrud for GetSite {
   ) -> Result<GetSiteResponse, LemmyError> {
     let data: &GetSite = self;
 
-    let site_view = match blocking(context.pool(), SiteView::read_local).await? {
-      Ok(site_view) => Some(site_view),
+    let site_view = match blocking(context.pool(), SiteView::read_local_opt).await?? {
+      Some(site_view) => Some(site_view),
       // If the site isn't created yet, check the setup
-      Err(_) => {
+      None => {
         if let Some(setup) = context.settings().setup.as_ref() {
           let register = Register {
             username: setup.admin_username.to_owned(),
@@ -132,7 +132,7 @@ impl PerformCrud for GetSite {
 
     let federated_instances = build_federated_instances(context.pool(), context.settings()).await?;
 
-    let all_languages = blocking(context.pool(), Language::read_all).await??;
+    let all_languages = blocking(context.pool(), |conn| Language::read_all(conn, true)).await??;
 
     Ok(GetSiteResponse {
       site_view,
@@ -1,5 +1,5 @@
 use crate::{
-  newtypes::{CommunityId, DbUrl, PersonId},
+  newtypes::{CommunityId, DbUrl, LanguageId, PersonId},
   source::community::{
     Community,
     CommunityFollower,
@@ -155,6 +155,20 @@ impl Community {
       ))
       .get_result::<Self>(conn)
   }
+
+  /// Counts the posts in a community per language, ordered by language id.
+  pub fn post_language_histogram(
+    conn: &mut PgConnection,
+    for_community_id: CommunityId,
+  ) -> Result<Vec<(LanguageId, i64)>, Error> {
+    use crate::schema::post::dsl::*;
+    post
+      .filter(community_id.eq(for_community_id))
+      .group_by(language_id)
+      .select((language_id, count_star()))
+      .order_by(language_id)
+      .load::<(LanguageId, i64)>(conn)
+  }
 }
 
 impl Joinable for CommunityModerator {
@@ -370,7 +384,7 @@ impl ApubActor for Community {
 #[cfg(test)]
 mod tests {
   use crate::{
-    source::{community::*, person::*},
+    source::{community::*, language::Language, person::*, post::*},
     traits::{Bannable, Crud, Followable, Joinable},
     utils::establish_unpooled_connection,
   };
@@ -491,4 +505,45 @@ mod tests {
     // assert_eq!(2, loaded_count);
     assert_eq!(1, num_deleted);
   }
+
+  #[test]
+  #[serial]
+  fn test_post_language_histogram() {
+    let conn = &mut establish_unpooled_connection();
+
+    let new_person = PersonForm {
+      name: "histogram_person".into(),
+      public_key: Some("pubkey".to_string()),
+      ..PersonForm::default()
+    };
+    let inserted_person = Person::create(conn, &new_person).unwrap();
+
+    let new_community = CommunityForm {
+      name: "histogram_community".into(),
+      title: "nada".to_owned(),
+      public_key: Some("pubkey".to_string()),
+      ..CommunityForm::default()
+    };
+    let inserted_community = Community::create(conn, &new_community).unwrap();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    for language_id in [english_id, english_id, french_id] {
+      let new_post = PostForm {
+        name: "A test post".into(),
+        creator_id: inserted_person.id,
+        community_id: inserted_community.id,
+        language_id: Some(language_id),
+        ..PostForm::default()
+      };
+      Post::create(conn, &new_post).unwrap();
+    }
+
+    let histogram = Community::post_language_histogram(conn, inserted_community.id).unwrap();
+
+    Community::delete(conn, inserted_community.id).unwrap();
+    Person::delete(conn, inserted_person.id).unwrap();
+
+    assert_eq!(vec![(english_id, 2), (french_id, 1)], histogram);
+  }
 }
@@ -1,10 +1,152 @@
-use crate::{newtypes::LanguageId, source::language::Language};
-use diesel::{result::Error, PgConnection, RunQueryDsl, *};
+use crate::{
+  newtypes::{LanguageId, LocalUserId},
+  source::language::Language,
+};
+use diesel::{dsl::count_star, result::Error, PgConnection, RunQueryDsl, *};
+use lemmy_utils::error::LemmyError;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
 
 impl Language {
-  pub fn read_all(conn: &mut PgConnection) -> Result<Vec<Language>, Error> {
+  /// Reads all languages. Deprecated languages are only included if `include_deprecated` is set,
+  /// they should not be offered for selection but existing content still references them.
+  pub fn read_all(
+    conn: &mut PgConnection,
+    include_deprecated: bool,
+  ) -> Result<Vec<Language>, Error> {
+    use crate::schema::language::dsl::*;
+    let mut query = language.into_boxed();
+    if !include_deprecated {
+      query = query.filter(deprecated.eq(false));
+    }
+    query.order_by(id).load::<Self>(conn)
+  }
+
+  /// Reads all languages, including deprecated ones, keyed by id.
+  pub fn read_all_indexed(conn: &mut PgConnection) -> Result<HashMap<LanguageId, Language>, Error> {
+    Ok(
+      Language::read_all(conn, true)?
+        .into_iter()
+        .map(|l| (l.id, l))
+        .collect(),
+    )
+  }
+
+  /// Reads all languages, most used first. Usage is the number of local users who selected the
+  /// language plus the number of posts written in it. Ties keep the id order. Deprecated languages
+  /// are left out.
+  pub fn read_all_by_popularity(conn: &mut PgConnection) -> Result<Vec<Language>, Error> {
+    use crate::schema::{local_user_language, post};
+
+    let mut usage = HashMap::new();
+    let user_counts = local_user_language::table
+      .group_by(local_user_language::language_id)
+      .select((local_user_language::language_id, count_star()))
+      .load::<(LanguageId, i64)>(conn)?;
+    let post_counts = post::table
+      .group_by(post::language_id)
+      .select((post::language_id, count_star()))
+      .load::<(LanguageId, i64)>(conn)?;
+    for (language_id, count) in user_counts.into_iter().chain(post_counts) {
+      *usage.entry(language_id).or_insert(0) += count;
+    }
+
+    let mut languages = Language::read_all(conn, false)?;
+    languages.sort_by_key(|l| std::cmp::Reverse(usage.get(&l.id).copied().unwrap_or(0)));
+    Ok(languages)
+  }
+
+  /// Reads all languages which no local user has selected and no post or comment is written in.
+  pub fn unused_languages(conn: &mut PgConnection) -> Result<Vec<Language>, Error> {
+    use crate::schema::{comment, language::dsl::*, local_user_language, post};
+    language
+      .filter(id.ne_all(local_user_language::table.select(local_user_language::language_id)))
+      .filter(id.ne_all(post::table.select(post::language_id)))
+      .filter(id.ne_all(comment::table.select(comment::language_id)))
+      .order_by(id)
+      .load::<Self>(conn)
+  }
+
+  /// Reads all languages for a language selector, with the languages selected by the given user
+  /// first. Both groups are sorted by name.
+  pub fn read_all_user_first(
+    conn: &mut PgConnection,
+    for_local_user_id: LocalUserId,
+  ) -> Result<Vec<Language>, Error> {
+    use crate::schema::local_user_language;
+
+    let selected = local_user_language::table
+      .filter(local_user_language::local_user_id.eq(for_local_user_id))
+      .select(local_user_language::language_id)
+      .load::<LanguageId>(conn)?;
+
+    let mut languages = Language::read_all(conn, false)?;
+    languages.sort_by_key(|l| (!selected.contains(&l.id), l.name.clone()));
+    Ok(languages)
+  }
+
+  /// Checks that the language table contains exactly the given codes, so that a broken or
+  /// missing migration is noticed early. The error lists missing and unexpected codes.
+  pub fn verify_seed(conn: &mut PgConnection, expected_codes: &[&str]) -> Result<(), LemmyError> {
+    let codes = Language::read_all(conn, true)?
+      .into_iter()
+      .map(|l| l.code)
+      .collect::<Vec<String>>();
+
+    let missing = expected_codes
+      .iter()
+      .filter(|e| !codes.iter().any(|c| c == *e))
+      .copied()
+      .collect::<Vec<&str>>();
+    let unexpected = codes
+      .iter()
+      .filter(|c| !expected_codes.contains(&c.as_str()))
+      .map(String::as_str)
+      .collect::<Vec<&str>>();
+
+    if missing.is_empty() && unexpected.is_empty() {
+      Ok(())
+    } else {
+      Err(LemmyError::from_message(&format!(
+        "language_seed_mismatch: missing [{}], unexpected [{}]",
+        missing.join(", "),
+        unexpected.join(", ")
+      )))
+    }
+  }
+
+  /// Changes the code of a language. Other tables reference languages by id, so they keep pointing
+  /// to the same language.
+  pub fn rename_code(
+    conn: &mut PgConnection,
+    old_code: &str,
+    new_code: &str,
+  ) -> Result<(), LemmyError> {
+    use crate::schema::language::dsl::*;
+    conn.build_transaction().read_write().run(|conn| {
+      let new_code_exists =
+        select(dsl::exists(language.filter(code.eq(new_code)))).get_result::<bool>(conn)?;
+      if new_code_exists {
+        return Err(LemmyError::from_message("language_code_already_exists"));
+      }
+
+      let language_id = Language::read_id_from_code(conn, old_code)?;
+      update(language.find(language_id))
+        .set(code.eq(new_code))
+        .execute(conn)?;
+      Ok(())
+    })
+  }
+
+  /// Returns the names of the given languages as a comma separated list, sorted by name.
+  pub fn display_list(conn: &mut PgConnection, ids: &[LanguageId]) -> Result<String, Error> {
     use crate::schema::language::dsl::*;
-    language.load::<Self>(conn)
+    let mut names = language
+      .filter(id.eq_any(ids))
+      .select(name)
+      .load::<String>(conn)?;
+    names.sort();
+    Ok(names.join(", "))
   }
 
   pub fn read_from_id(conn: &mut PgConnection, id_: LanguageId) -> Result<Language, Error> {
@@ -17,6 +159,19 @@ impl Language {
     Ok(language.filter(code.eq(code_)).first::<Self>(conn)?.id)
   }
 
+  /// Maps the given codes to language ids. Codes without a language are left out.
+  pub fn code_id_map(
+    conn: &mut PgConnection,
+    codes: &[String],
+  ) -> Result<HashMap<String, LanguageId>, Error> {
+    use crate::schema::language::dsl::*;
+    let pairs = language
+      .filter(code.eq_any(codes))
+      .select((code, id))
+      .load::<(String, LanguageId)>(conn)?;
+    Ok(pairs.into_iter().collect())
+  }
+
   pub fn read_id_from_code_opt(
     conn: &mut PgConnection,
     code_: Option<&str>,
@@ -32,11 +187,27 @@ impl Language {
     use crate::schema::language::dsl::*;
     Ok(language.filter(code.eq("und")).first::<Self>(conn)?.id)
   }
+
+  /// Same as [`Language::read_undetermined`], but only queries the database once.
+  pub fn undetermined_id(conn: &mut PgConnection) -> Result<LanguageId, Error> {
+    static UNDETERMINED_ID: OnceCell<LanguageId> = OnceCell::new();
+    Ok(*UNDETERMINED_ID.get_or_try_init(|| Language::read_undetermined(conn))?)
+  }
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::{source::language::Language, utils::establish_unpooled_connection};
+  use crate::{
+    schema::{language, local_user_language},
+    source::{
+      language::Language,
+      local_user_language::LocalUserLanguage,
+      person::Person,
+    },
+    traits::Crud,
+    utils::{create_local_user, establish_unpooled_connection},
+  };
+  use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
   use serial_test::serial;
 
   #[test]
@@ -44,11 +215,200 @@ mod tests {
   fn test_languages() {
     let conn = &mut establish_unpooled_connection();
 
-    let all = Language::read_all(conn).unwrap();
+    let all = Language::read_all(conn, true).unwrap();
 
     assert_eq!(184, all.len());
     assert_eq!("ak", all[5].code);
     assert_eq!("lv", all[99].code);
     assert_eq!("yi", all[179].code);
   }
+
+  #[test]
+  #[serial]
+  fn test_read_all_deprecated() {
+    let conn = &mut establish_unpooled_connection();
+
+    let finnish_id = Language::read_id_from_code(conn, "fi").unwrap();
+    let set_deprecated = |conn: &mut PgConnection, deprecated: bool| {
+      diesel::update(language::table.find(finnish_id))
+        .set(language::deprecated.eq(deprecated))
+        .execute(conn)
+        .unwrap();
+    };
+    set_deprecated(conn, true);
+
+    let without_deprecated = Language::read_all(conn, false).unwrap();
+    let with_deprecated = Language::read_all(conn, true).unwrap();
+    let by_popularity = Language::read_all_by_popularity(conn).unwrap();
+    let finnish = Language::read_from_id(conn, finnish_id).unwrap();
+
+    set_deprecated(conn, false);
+
+    assert_eq!(183, without_deprecated.len());
+    assert!(!without_deprecated.iter().any(|l| l.id == finnish_id));
+    assert_eq!(184, with_deprecated.len());
+    assert_eq!(183, by_popularity.len());
+    assert!(!by_popularity.iter().any(|l| l.id == finnish_id));
+    assert_eq!("fi", finnish.code);
+    assert!(finnish.deprecated);
+  }
+
+  #[test]
+  #[serial]
+  fn test_read_all_indexed() {
+    let conn = &mut establish_unpooled_connection();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let indexed = Language::read_all_indexed(conn).unwrap();
+
+    assert_eq!(184, indexed.len());
+    assert_eq!("en", indexed[&english_id].code);
+  }
+
+  #[test]
+  #[serial]
+  fn test_code_id_map() {
+    let conn = &mut establish_unpooled_connection();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let codes = ["en", "zz"].map(String::from);
+    let map = Language::code_id_map(conn, &codes).unwrap();
+
+    assert_eq!(1, map.len());
+    assert_eq!(Some(&english_id), map.get("en"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_display_list() {
+    let conn = &mut establish_unpooled_connection();
+
+    let ids = ["ru", "en", "fr"].map(|c| Language::read_id_from_code(conn, c).unwrap());
+    assert_eq!(
+      "English, Français, Русский",
+      Language::display_list(conn, &ids).unwrap()
+    );
+  }
+
+  #[test]
+  #[serial]
+  fn test_undetermined_id() {
+    let conn = &mut establish_unpooled_connection();
+
+    let undetermined_id = Language::read_id_from_code(conn, "und").unwrap();
+    assert_eq!(undetermined_id, Language::undetermined_id(conn).unwrap());
+    // second call is served from the cache
+    assert_eq!(undetermined_id, Language::undetermined_id(conn).unwrap());
+  }
+
+  #[test]
+  #[serial]
+  fn test_languages_by_popularity() {
+    let conn = &mut establish_unpooled_connection();
+
+    let finnish_id = Language::read_id_from_code(conn, "fi").unwrap();
+    let polish_id = Language::read_id_from_code(conn, "pl").unwrap();
+    let mut persons = vec![];
+    for (name, languages) in [
+      ("popular_lang_a", vec![finnish_id, polish_id]),
+      ("popular_lang_b", vec![finnish_id]),
+    ] {
+      let (person, local_user) = create_local_user(conn, name);
+      LocalUserLanguage::update_user_languages(conn, Some(languages), local_user.id).unwrap();
+      persons.push(person);
+    }
+
+    let by_popularity = Language::read_all_by_popularity(conn).unwrap();
+
+    for person in persons {
+      Person::delete(conn, person.id).unwrap();
+    }
+
+    assert_eq!(184, by_popularity.len());
+    assert_eq!("fi", by_popularity[0].code);
+    assert_eq!("pl", by_popularity[1].code);
+  }
+
+  #[test]
+  #[serial]
+  fn test_unused_languages() {
+    let conn = &mut establish_unpooled_connection();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let (person, local_user) = create_local_user(conn, "unused_lang");
+    LocalUserLanguage::update_user_languages(conn, Some(vec![english_id]), local_user.id).unwrap();
+
+    let unused = Language::unused_languages(conn).unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(183, unused.len());
+    assert!(!unused.iter().any(|l| l.code == "en"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_rename_code() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "rename_lang");
+    let finnish_id = Language::read_id_from_code(conn, "fi").unwrap();
+    LocalUserLanguage::update_user_languages(conn, Some(vec![finnish_id]), local_user.id).unwrap();
+
+    Language::rename_code(conn, "fi", "zz").unwrap();
+    let renamed_id = Language::read_id_from_code(conn, "zz");
+    let user_language = local_user_language::table
+      .filter(local_user_language::local_user_id.eq(local_user.id))
+      .inner_join(language::table)
+      .select(language::code)
+      .first::<String>(conn);
+    let duplicate = Language::rename_code(conn, "zz", "en");
+
+    Language::rename_code(conn, "zz", "fi").unwrap();
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(finnish_id, renamed_id.unwrap());
+    assert_eq!("zz", user_language.unwrap());
+    assert_eq!(
+      Some("language_code_already_exists".to_string()),
+      duplicate.unwrap_err().message
+    );
+  }
+
+  #[test]
+  #[serial]
+  fn test_read_all_user_first() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "lang_user_first");
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    LocalUserLanguage::update_user_languages(conn, Some(vec![french_id]), local_user.id).unwrap();
+
+    let languages = Language::read_all_user_first(conn, local_user.id).unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(184, languages.len());
+    assert_eq!("fr", languages[0].code);
+    // the rest is sorted by name
+    assert!(languages[1..].windows(2).all(|w| w[0].name <= w[1].name));
+  }
+
+  #[test]
+  #[serial]
+  fn test_verify_seed() {
+    let conn = &mut establish_unpooled_connection();
+
+    let all = Language::read_all(conn, true).unwrap();
+    let mut expected = all.iter().map(|l| l.code.as_str()).collect::<Vec<&str>>();
+    assert!(Language::verify_seed(conn, &expected).is_ok());
+
+    expected.retain(|c| *c != "en");
+    expected.push("zz");
+    let err = Language::verify_seed(conn, &expected).unwrap_err();
+    assert_eq!(
+      Some("language_seed_mismatch: missing [zz], unexpected [en]".to_string()),
+      err.message
+    );
+  }
 }
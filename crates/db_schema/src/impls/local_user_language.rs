@@ -3,11 +3,13 @@ use crate::{
   source::{language::Language, local_user_language::*},
 };
 use diesel::{result::Error, PgConnection, RunQueryDsl, *};
+use lemmy_utils::error::LemmyError;
+use std::collections::HashSet;
 
 impl LocalUserLanguage {
   /// Update the user's languages.
   ///
-  /// If no language_id vector is given, it will show all languages
+  /// If no language_id vector is given, it will show all languages which aren't deprecated
   pub fn update_user_languages(
     conn: &mut PgConnection,
     language_ids: Option<Vec<LanguageId>>,
@@ -17,7 +19,7 @@ impl LocalUserLanguage {
 
     // If no language is given, read all languages
     let lang_ids = language_ids.unwrap_or(
-      Language::read_all(conn)?
+      Language::read_all(conn, false)?
         .into_iter()
         .map(|l| l.id)
         .collect(),
@@ -39,4 +41,628 @@ impl LocalUserLanguage {
       Ok(())
     })
   }
+
+  /// Sets the user's languages from language codes, for example when importing a user from
+  /// another instance. Unknown codes are skipped. If no code is known, the user gets all
+  /// languages, like for an empty selection.
+  pub fn set_from_codes(
+    conn: &mut PgConnection,
+    for_local_user_id: LocalUserId,
+    codes: &[String],
+  ) -> Result<(), Error> {
+    use crate::schema::language;
+
+    let lang_ids = language::table
+      .filter(language::code.eq_any(codes))
+      .select(language::id)
+      .load::<LanguageId>(conn)?;
+    let lang_ids = if lang_ids.is_empty() {
+      None
+    } else {
+      Some(lang_ids)
+    };
+    LocalUserLanguage::update_user_languages(conn, lang_ids, for_local_user_id)
+  }
+
+  /// Returns the user's languages which are also in `new_site_languages`, to preview what a
+  /// change of the allowed languages would leave the user with. Nothing is written.
+  pub fn preview_after_site_limit(
+    conn: &mut PgConnection,
+    for_local_user_id: LocalUserId,
+    new_site_languages: &[LanguageId],
+  ) -> Result<Vec<LanguageId>, Error> {
+    use crate::schema::local_user_language::dsl::*;
+    local_user_language
+      .filter(local_user_id.eq(for_local_user_id))
+      .filter(language_id.eq_any(new_site_languages))
+      .select(language_id)
+      .order_by(language_id)
+      .load::<LanguageId>(conn)
+  }
+
+  /// Returns the languages to filter the user's feed by, or `None` if the user has all languages
+  /// selected and no filter is needed. Deprecated languages don't count towards all languages.
+  pub fn feed_filter(
+    conn: &mut PgConnection,
+    for_local_user_id: LocalUserId,
+  ) -> Result<Option<Vec<LanguageId>>, Error> {
+    use crate::schema::{language, local_user_language};
+
+    conn
+      .build_transaction()
+      .repeatable_read()
+      .read_only()
+      .run(|conn| {
+        let lang_ids = local_user_language::table
+          .filter(local_user_language::local_user_id.eq(for_local_user_id))
+          .select(local_user_language::language_id)
+          .order_by(local_user_language::language_id)
+          .load::<LanguageId>(conn)?;
+        let missing_count = language::table
+          .filter(language::deprecated.eq(false))
+          .filter(language::id.ne_all(&lang_ids))
+          .count()
+          .get_result::<i64>(conn)?;
+
+        if missing_count == 0 {
+          Ok(None)
+        } else {
+          Ok(Some(lang_ids))
+        }
+      })
+  }
+
+  /// Reads all languages which no local user has selected.
+  pub fn never_selected(conn: &mut PgConnection) -> Result<Vec<Language>, Error> {
+    use crate::schema::{language, local_user_language};
+    language::table
+      .filter(
+        language::id.ne_all(local_user_language::table.select(local_user_language::language_id)),
+      )
+      .order_by(language::id)
+      .load::<Language>(conn)
+  }
+
+  /// Resolves the language to use when a user posts without choosing one. This is the user's
+  /// only selected language, or undetermined if the user selected none or several.
+  pub fn resolve_auto_language(
+    conn: &mut PgConnection,
+    for_local_user_id: LocalUserId,
+  ) -> Result<LanguageId, Error> {
+    use crate::schema::local_user_language::dsl::*;
+    let lang_ids = local_user_language
+      .filter(local_user_id.eq(for_local_user_id))
+      .select(language_id)
+      .limit(2)
+      .load::<LanguageId>(conn)?;
+    match lang_ids[..] {
+      [single] => Ok(single),
+      _ => Language::undetermined_id(conn),
+    }
+  }
+
+  /// Updates the user's languages to the given codes, for example from a federated person
+  /// update. Unknown codes are skipped, and only the changed rows are written. If no code is
+  /// known, nothing is changed, so that the user doesn't end up without languages.
+  pub fn reconcile_from_codes(
+    conn: &mut PgConnection,
+    for_local_user_id: LocalUserId,
+    codes: &[String],
+  ) -> Result<LanguageUpdateResult, Error> {
+    use crate::schema::{language, local_user_language::dsl::*};
+
+    conn.build_transaction().read_write().run(|conn| {
+      let new_lang_ids = language::table
+        .filter(language::code.eq_any(codes))
+        .select(language::id)
+        .load::<LanguageId>(conn)?;
+      if new_lang_ids.is_empty() {
+        return Ok(LanguageUpdateResult {
+          added: vec![],
+          removed: vec![],
+        });
+      }
+      let current_lang_ids = local_user_language
+        .filter(local_user_id.eq(for_local_user_id))
+        .select(language_id)
+        .load::<LanguageId>(conn)?;
+      let (added, removed) =
+        LocalUserLanguage::symmetric_difference(&new_lang_ids, &current_lang_ids);
+
+      delete(
+        local_user_language
+          .filter(local_user_id.eq(for_local_user_id))
+          .filter(language_id.eq_any(&removed)),
+      )
+      .execute(conn)?;
+      for l in &added {
+        let form = LocalUserLanguageForm {
+          local_user_id: for_local_user_id,
+          language_id: *l,
+        };
+        insert_into(local_user_language)
+          .values(form)
+          .on_conflict((local_user_id, language_id))
+          .do_nothing()
+          .execute(conn)?;
+      }
+      Ok(LanguageUpdateResult { added, removed })
+    })
+  }
+
+  /// Adds all languages of one local user to another, keeping the languages the target user
+  /// already has. The source user's languages are left unchanged.
+  pub fn merge_into(
+    conn: &mut PgConnection,
+    from_local_user_id: LocalUserId,
+    into_local_user_id: LocalUserId,
+  ) -> Result<(), Error> {
+    use crate::schema::local_user_language::dsl::*;
+
+    conn.build_transaction().read_write().run(|conn| {
+      let lang_ids = local_user_language
+        .filter(local_user_id.eq(from_local_user_id))
+        .select(language_id)
+        .load::<LanguageId>(conn)?;
+
+      for l in lang_ids {
+        let form = LocalUserLanguageForm {
+          local_user_id: into_local_user_id,
+          language_id: l,
+        };
+        insert_into(local_user_language)
+          .values(form)
+          .on_conflict((local_user_id, language_id))
+          .do_nothing()
+          .execute(conn)?;
+      }
+      Ok(())
+    })
+  }
+
+  /// Compares two language selections as sets. An empty selection stands for all languages, so
+  /// it is equal to the full list of languages.
+  pub fn sets_equivalent(
+    conn: &mut PgConnection,
+    a: &[LanguageId],
+    b: &[LanguageId],
+  ) -> Result<bool, Error> {
+    let all = if a.is_empty() || b.is_empty() {
+      Language::read_all(conn, false)?
+        .into_iter()
+        .map(|l| l.id)
+        .collect()
+    } else {
+      vec![]
+    };
+    let normalize = |ids: &[LanguageId]| -> HashSet<LanguageId> {
+      if ids.is_empty() {
+        all.iter().copied().collect()
+      } else {
+        ids.iter().copied().collect()
+      }
+    };
+    Ok(normalize(a) == normalize(b))
+  }
+
+  /// Adds `new_language_id` to every local user who has `trigger_language_id` selected, for
+  /// example to enable a new variant of a language. Returns the number of users changed.
+  pub fn enable_for_users_with(
+    conn: &mut PgConnection,
+    trigger_language_id: LanguageId,
+    new_language_id: LanguageId,
+  ) -> Result<usize, Error> {
+    use crate::schema::local_user_language::dsl::*;
+
+    conn.build_transaction().read_write().run(|conn| {
+      let local_user_ids = local_user_language
+        .filter(language_id.eq(trigger_language_id))
+        .select(local_user_id)
+        .load::<LocalUserId>(conn)?;
+
+      let mut changed = 0;
+      for l in local_user_ids {
+        let form = LocalUserLanguageForm {
+          local_user_id: l,
+          language_id: new_language_id,
+        };
+        changed += insert_into(local_user_language)
+          .values(form)
+          .on_conflict((local_user_id, language_id))
+          .do_nothing()
+          .execute(conn)?;
+      }
+      Ok(changed)
+    })
+  }
+
+  /// Cleans up language ids sent by a client: removes duplicates and ids which don't exist.
+  /// Returns an error if none of the given ids is valid. An empty input is returned as is.
+  pub fn sanitize_input(
+    conn: &mut PgConnection,
+    ids: Vec<LanguageId>,
+  ) -> Result<Vec<LanguageId>, LemmyError> {
+    use crate::schema::language;
+
+    if ids.is_empty() {
+      return Ok(ids);
+    }
+
+    let existing = language::table
+      .filter(language::id.eq_any(&ids))
+      .select(language::id)
+      .load::<LanguageId>(conn)?;
+    let mut sanitized = vec![];
+    for l in ids {
+      if existing.contains(&l) && !sanitized.contains(&l) {
+        sanitized.push(l);
+      }
+    }
+
+    if sanitized.is_empty() {
+      Err(LemmyError::from_message("invalid_language_id"))
+    } else {
+      Ok(sanitized)
+    }
+  }
+
+  /// Splits two language selections into the languages only found in `a` and the languages only
+  /// found in `b`, keeping the input order.
+  pub fn symmetric_difference(
+    a: &[LanguageId],
+    b: &[LanguageId],
+  ) -> (Vec<LanguageId>, Vec<LanguageId>) {
+    let only_in_a = a.iter().filter(|l| !b.contains(l)).copied().collect();
+    let only_in_b = b.iter().filter(|l| !a.contains(l)).copied().collect();
+    (only_in_a, only_in_b)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    newtypes::LanguageId,
+    schema::{language, local_user_language},
+    source::{
+      language::Language,
+      local_user_language::{LanguageUpdateResult, LocalUserLanguage},
+      person::Person,
+    },
+    traits::Crud,
+    utils::{create_local_user, establish_unpooled_connection},
+  };
+  use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_merge_into() {
+    let conn = &mut establish_unpooled_connection();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    let russian_id = Language::read_id_from_code(conn, "ru").unwrap();
+    let mut persons = vec![];
+    let mut local_users = vec![];
+    for (name, languages) in [
+      ("merge_lang_from", vec![english_id, french_id]),
+      ("merge_lang_into", vec![french_id, russian_id]),
+    ] {
+      let (person, local_user) = create_local_user(conn, name);
+      LocalUserLanguage::update_user_languages(conn, Some(languages), local_user.id).unwrap();
+      persons.push(person);
+      local_users.push(local_user);
+    }
+
+    LocalUserLanguage::merge_into(conn, local_users[0].id, local_users[1].id).unwrap();
+    let merged = local_user_language::table
+      .filter(local_user_language::local_user_id.eq(local_users[1].id))
+      .select(local_user_language::language_id)
+      .order_by(local_user_language::language_id)
+      .load::<LanguageId>(conn)
+      .unwrap();
+
+    for person in persons {
+      Person::delete(conn, person.id).unwrap();
+    }
+
+    let mut expected = vec![english_id, french_id, russian_id];
+    expected.sort_by_key(|l| l.0);
+    assert_eq!(expected, merged);
+  }
+
+  #[test]
+  #[serial]
+  fn test_set_from_codes() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "lang_from_codes");
+
+    let codes = ["en", "fr", "zz"].map(String::from);
+    LocalUserLanguage::set_from_codes(conn, local_user.id, &codes).unwrap();
+    let languages = local_user_language::table
+      .filter(local_user_language::local_user_id.eq(local_user.id))
+      .select(local_user_language::language_id)
+      .order_by(local_user_language::language_id)
+      .load::<LanguageId>(conn)
+      .unwrap();
+
+    // only unknown codes give all languages instead of an empty selection
+    let unknown_codes = ["zz".to_string()];
+    LocalUserLanguage::set_from_codes(conn, local_user.id, &unknown_codes).unwrap();
+    let unknown_languages = LocalUserLanguage::feed_filter(conn, local_user.id).unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    assert_eq!(vec![english_id, french_id], languages);
+    assert_eq!(None, unknown_languages);
+  }
+
+  #[test]
+  #[serial]
+  fn test_reconcile_from_codes() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "lang_reconcile");
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    let german_id = Language::read_id_from_code(conn, "de").unwrap();
+    LocalUserLanguage::update_user_languages(
+      conn,
+      Some(vec![english_id, french_id]),
+      local_user.id,
+    )
+    .unwrap();
+
+    let codes = ["en", "de"].map(String::from);
+    let result = LocalUserLanguage::reconcile_from_codes(conn, local_user.id, &codes).unwrap();
+    // unknown codes leave the languages unchanged
+    let unknown_codes = ["zz".to_string()];
+    let unknown_result =
+      LocalUserLanguage::reconcile_from_codes(conn, local_user.id, &unknown_codes).unwrap();
+    let empty_result = LocalUserLanguage::reconcile_from_codes(conn, local_user.id, &[]).unwrap();
+    let languages = local_user_language::table
+      .filter(local_user_language::local_user_id.eq(local_user.id))
+      .select(local_user_language::language_id)
+      .order_by(local_user_language::language_id)
+      .load::<LanguageId>(conn)
+      .unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    let expected_result = LanguageUpdateResult {
+      added: vec![german_id],
+      removed: vec![french_id],
+    };
+    assert_eq!(expected_result, result);
+    let unchanged_result = LanguageUpdateResult {
+      added: vec![],
+      removed: vec![],
+    };
+    assert_eq!(unchanged_result, unknown_result);
+    assert_eq!(unchanged_result, empty_result);
+    let mut expected_languages = vec![english_id, german_id];
+    expected_languages.sort_by_key(|l| l.0);
+    assert_eq!(expected_languages, languages);
+  }
+
+  #[test]
+  #[serial]
+  fn test_preview_after_site_limit() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "lang_site_limit");
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    let russian_id = Language::read_id_from_code(conn, "ru").unwrap();
+    let finnish_id = Language::read_id_from_code(conn, "fi").unwrap();
+    LocalUserLanguage::update_user_languages(
+      conn,
+      Some(vec![english_id, french_id, russian_id]),
+      local_user.id,
+    )
+    .unwrap();
+
+    let preview =
+      LocalUserLanguage::preview_after_site_limit(conn, local_user.id, &[english_id, finnish_id])
+        .unwrap();
+    let unchanged = local_user_language::table
+      .filter(local_user_language::local_user_id.eq(local_user.id))
+      .count()
+      .get_result::<i64>(conn)
+      .unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(vec![english_id], preview);
+    assert_eq!(3, unchanged);
+  }
+
+  #[test]
+  #[serial]
+  fn test_feed_filter() {
+    let conn = &mut establish_unpooled_connection();
+
+    // new users have all languages selected
+    let (person, local_user) = create_local_user(conn, "lang_feed_filter");
+    let all_languages = LocalUserLanguage::feed_filter(conn, local_user.id).unwrap();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    LocalUserLanguage::update_user_languages(conn, Some(vec![english_id]), local_user.id).unwrap();
+    let restricted = LocalUserLanguage::feed_filter(conn, local_user.id).unwrap();
+
+    // selecting every offered language means all languages, even with a deprecated one left out
+    let finnish_id = Language::read_id_from_code(conn, "fi").unwrap();
+    let set_deprecated = |conn: &mut PgConnection, deprecated: bool| {
+      diesel::update(language::table.find(finnish_id))
+        .set(language::deprecated.eq(deprecated))
+        .execute(conn)
+        .unwrap();
+    };
+    set_deprecated(conn, true);
+    let offered = Language::read_all(conn, false)
+      .unwrap()
+      .into_iter()
+      .map(|l| l.id)
+      .collect();
+    LocalUserLanguage::update_user_languages(conn, Some(offered), local_user.id).unwrap();
+    let all_offered = LocalUserLanguage::feed_filter(conn, local_user.id).unwrap();
+    set_deprecated(conn, false);
+
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(None, all_languages);
+    assert_eq!(Some(vec![english_id]), restricted);
+    assert_eq!(None, all_offered);
+  }
+
+  #[test]
+  #[serial]
+  fn test_never_selected() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "lang_never_selected");
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    LocalUserLanguage::update_user_languages(conn, Some(vec![english_id]), local_user.id).unwrap();
+
+    let never_selected = LocalUserLanguage::never_selected(conn).unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(183, never_selected.len());
+    assert!(!never_selected.iter().any(|l| l.id == english_id));
+  }
+
+  #[test]
+  #[serial]
+  fn test_resolve_auto_language() {
+    let conn = &mut establish_unpooled_connection();
+
+    let (person, local_user) = create_local_user(conn, "lang_auto");
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    let undetermined_id = Language::read_id_from_code(conn, "und").unwrap();
+
+    LocalUserLanguage::update_user_languages(conn, Some(vec![english_id]), local_user.id).unwrap();
+    let single = LocalUserLanguage::resolve_auto_language(conn, local_user.id).unwrap();
+
+    LocalUserLanguage::update_user_languages(
+      conn,
+      Some(vec![english_id, french_id]),
+      local_user.id,
+    )
+    .unwrap();
+    let multiple = LocalUserLanguage::resolve_auto_language(conn, local_user.id).unwrap();
+
+    Person::delete(conn, person.id).unwrap();
+
+    assert_eq!(english_id, single);
+    assert_eq!(undetermined_id, multiple);
+  }
+
+  #[test]
+  #[serial]
+  fn test_enable_for_users_with() {
+    let conn = &mut establish_unpooled_connection();
+
+    let german_id = Language::read_id_from_code(conn, "de").unwrap();
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let new_language_id = Language::read_id_from_code(conn, "lb").unwrap();
+    let mut persons = vec![];
+    let mut local_users = vec![];
+    for (name, languages) in [
+      ("enable_lang_de", vec![german_id]),
+      ("enable_lang_de_new", vec![german_id, new_language_id]),
+      ("enable_lang_en", vec![english_id]),
+    ] {
+      let (person, local_user) = create_local_user(conn, name);
+      LocalUserLanguage::update_user_languages(conn, Some(languages), local_user.id).unwrap();
+      persons.push(person);
+      local_users.push(local_user);
+    }
+
+    let changed =
+      LocalUserLanguage::enable_for_users_with(conn, german_id, new_language_id).unwrap();
+    let mut has_new_language = vec![];
+    for local_user in &local_users {
+      let has = local_user_language::table
+        .filter(local_user_language::local_user_id.eq(local_user.id))
+        .filter(local_user_language::language_id.eq(new_language_id))
+        .count()
+        .get_result::<i64>(conn)
+        .unwrap();
+      has_new_language.push(has == 1);
+    }
+
+    for person in persons {
+      Person::delete(conn, person.id).unwrap();
+    }
+
+    assert_eq!(1, changed);
+    assert_eq!(vec![true, true, false], has_new_language);
+  }
+
+  #[test]
+  #[serial]
+  fn test_sets_equivalent() {
+    let conn = &mut establish_unpooled_connection();
+
+    let all = Language::read_all(conn, false)
+      .unwrap()
+      .into_iter()
+      .map(|l| l.id)
+      .collect::<Vec<LanguageId>>();
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+
+    assert!(LocalUserLanguage::sets_equivalent(conn, &[], &all).unwrap());
+    assert!(!LocalUserLanguage::sets_equivalent(conn, &[english_id], &[]).unwrap());
+  }
+
+  #[test]
+  #[serial]
+  fn test_sanitize_input() {
+    let conn = &mut establish_unpooled_connection();
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    let invalid_id = LanguageId(-1);
+
+    let deduped =
+      LocalUserLanguage::sanitize_input(conn, vec![english_id, french_id, english_id]).unwrap();
+    assert_eq!(vec![english_id, french_id], deduped);
+
+    let valid_only = LocalUserLanguage::sanitize_input(conn, vec![invalid_id, english_id]).unwrap();
+    assert_eq!(vec![english_id], valid_only);
+
+    let err = LocalUserLanguage::sanitize_input(conn, vec![invalid_id]).unwrap_err();
+    assert_eq!(Some("invalid_language_id".to_string()), err.message);
+  }
+
+  #[test]
+  fn test_symmetric_difference() {
+    let (en, fr, ru, de) = (
+      LanguageId(37),
+      LanguageId(47),
+      LanguageId(122),
+      LanguageId(32),
+    );
+
+    // overlapping sets
+    assert_eq!(
+      (vec![en], vec![ru]),
+      LocalUserLanguage::symmetric_difference(&[en, fr], &[fr, ru])
+    );
+    // disjoint sets
+    assert_eq!(
+      (vec![en, fr], vec![ru, de]),
+      LocalUserLanguage::symmetric_difference(&[en, fr], &[ru, de])
+    );
+    // equal sets
+    assert_eq!(
+      (vec![], vec![]),
+      LocalUserLanguage::symmetric_difference(&[en, fr], &[fr, en])
+    );
+  }
 }
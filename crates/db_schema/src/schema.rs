@@ -624,6 +624,7 @@ table! {
         id -> Int4,
         code -> Text,
         name -> Text,
+        deprecated -> Bool,
     }
 }
 
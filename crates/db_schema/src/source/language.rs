@@ -11,4 +11,5 @@ pub struct Language {
   pub id: LanguageId,
   pub code: String,
   pub name: String,
+  pub deprecated: bool,
 }
@@ -21,3 +21,10 @@ pub struct LocalUserLanguageForm {
   pub local_user_id: LocalUserId,
   pub language_id: LanguageId,
 }
+
+/// The languages added to and removed from a user by a language update.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LanguageUpdateResult {
+  pub added: Vec<LanguageId>,
+  pub removed: Vec<LanguageId>,
+}
@@ -118,6 +118,38 @@ pub fn establish_unpooled_connection() -> PgConnection {
   conn
 }
 
+/// Creates a person with a local user, for tests which need a local user.
+#[cfg(test)]
+pub(crate) fn create_local_user(
+  conn: &mut PgConnection,
+  name: &str,
+) -> (
+  crate::source::person::Person,
+  crate::source::local_user::LocalUser,
+) {
+  use crate::{
+    source::{
+      local_user::{LocalUser, LocalUserForm},
+      person::{Person, PersonForm},
+    },
+    traits::Crud,
+  };
+
+  let person_form = PersonForm {
+    name: name.into(),
+    public_key: Some("pubkey".to_string()),
+    ..PersonForm::default()
+  };
+  let person = Person::create(conn, &person_form).unwrap();
+  let local_user_form = LocalUserForm {
+    person_id: Some(person.id),
+    password_encrypted: Some("nada".to_string()),
+    ..LocalUserForm::default()
+  };
+  let local_user = LocalUser::create(conn, &local_user_form).unwrap();
+  (person, local_user)
+}
+
 pub fn naive_now() -> NaiveDateTime {
   chrono::prelude::Utc::now().naive_utc()
 }
@@ -3,7 +3,7 @@ use diesel::{dsl::*, result::Error, *};
 use diesel_ltree::{nlevel, subpath, Ltree, LtreeExtensions};
 use lemmy_db_schema::{
   aggregates::structs::CommentAggregates,
-  newtypes::{CommentId, CommunityId, DbUrl, LocalUserId, PersonId, PostId},
+  newtypes::{CommentId, CommunityId, DbUrl, LanguageId, LocalUserId, PersonId, PostId},
   schema::{
     comment,
     comment_aggregates,
@@ -165,6 +165,7 @@ pub struct CommentQuery<'a> {
   search_term: Option<String>,
   saved_only: Option<bool>,
   show_deleted_and_removed: Option<bool>,
+  language_ids: Option<Vec<LanguageId>>,
   page: Option<i64>,
   limit: Option<i64>,
   max_depth: Option<i32>,
@@ -306,6 +307,10 @@ impl<'a> CommentQuery<'a> {
       query = query.filter(comment::removed.eq(false));
     }
 
+    if let Some(language_ids) = self.language_ids {
+      query = query.filter(comment::language_id.eq_any(language_ids));
+    }
+
     if !self.local_user.map(|l| l.show_bot_accounts).unwrap_or(true) {
       query = query.filter(person::bot_account.eq(false));
     };
@@ -745,6 +750,29 @@ mod tests {
     cleanup(data, conn);
   }
 
+  #[test]
+  #[serial]
+  fn test_language_ids() {
+    let conn = &mut establish_unpooled_connection();
+    let data = init_data(conn);
+
+    let finnish_id = Language::read_id_from_code(conn, "fi").unwrap();
+    let polish_id = Language::read_id_from_code(conn, "pl").unwrap();
+    let finnish_polish_comments = CommentQuery::builder()
+      .conn(conn)
+      .post_id(Some(data.inserted_post.id))
+      .language_ids(Some(vec![finnish_id, polish_id]))
+      .build()
+      .list()
+      .unwrap();
+    assert_eq!(2, finnish_polish_comments.len());
+    assert!(finnish_polish_comments
+      .iter()
+      .all(|c| c.comment.language_id == finnish_id || c.comment.language_id == polish_id));
+
+    cleanup(data, conn);
+  }
+
   fn cleanup(data: Data, conn: &mut PgConnection) {
     CommentLike::remove(conn, data.inserted_person.id, data.inserted_comment_0.id).unwrap();
     Comment::delete(conn, data.inserted_comment_0.id).unwrap();
@@ -25,8 +25,63 @@ impl LocalUserDiscussionLanguageView {
         language::all_columns,
       ))
       .filter(local_user::id.eq(local_user_id))
+      .order_by(language::id)
       .load::<LocalUserDiscussionLanguageViewTuple>(conn)?;
 
     Ok(res.into_iter().map(|a| a.1).collect::<Vec<Language>>())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::structs::LocalUserDiscussionLanguageView;
+  use lemmy_db_schema::{
+    source::{
+      language::Language,
+      local_user::{LocalUser, LocalUserForm},
+      local_user_language::LocalUserLanguage,
+      person::*,
+    },
+    traits::Crud,
+    utils::establish_unpooled_connection,
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_read_languages_ordered() {
+    let conn = &mut establish_unpooled_connection();
+
+    let person_form = PersonForm {
+      name: "language_order_person".into(),
+      public_key: Some("pubkey".to_string()),
+      ..PersonForm::default()
+    };
+    let inserted_person = Person::create(conn, &person_form).unwrap();
+
+    let local_user_form = LocalUserForm {
+      person_id: Some(inserted_person.id),
+      password_encrypted: Some("nada".to_string()),
+      ..LocalUserForm::default()
+    };
+    let inserted_local_user = LocalUser::create(conn, &local_user_form).unwrap();
+
+    let russian_id = Language::read_id_from_code(conn, "ru").unwrap();
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let french_id = Language::read_id_from_code(conn, "fr").unwrap();
+    LocalUserLanguage::update_user_languages(
+      conn,
+      Some(vec![russian_id, english_id, french_id]),
+      inserted_local_user.id,
+    )
+    .unwrap();
+
+    let languages =
+      LocalUserDiscussionLanguageView::read_languages(conn, inserted_local_user.id).unwrap();
+    let language_ids = languages.into_iter().map(|l| l.id).collect::<Vec<_>>();
+
+    Person::delete(conn, inserted_person.id).unwrap();
+
+    assert_eq!(vec![english_id, french_id, russian_id], language_ids);
+  }
+}
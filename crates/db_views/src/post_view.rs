@@ -2,7 +2,7 @@ use crate::structs::PostView;
 use diesel::{dsl::*, pg::Pg, result::Error, *};
 use lemmy_db_schema::{
   aggregates::structs::PostAggregates,
-  newtypes::{CommunityId, DbUrl, LocalUserId, PersonId, PostId},
+  newtypes::{CommunityId, DbUrl, LanguageId, LocalUserId, PersonId, PostId},
   schema::{
     community,
     community_block,
@@ -184,6 +184,7 @@ pub struct PostQuery<'a> {
   search_term: Option<String>,
   url_search: Option<String>,
   saved_only: Option<bool>,
+  language_ids: Option<Vec<LanguageId>>,
   page: Option<i64>,
   limit: Option<i64>,
 }
@@ -338,6 +339,10 @@ impl<'a> PostQuery<'a> {
       query = query.filter(post::creator_id.eq(creator_id));
     }
 
+    if let Some(language_ids) = self.language_ids {
+      query = query.filter(post::language_id.eq_any(language_ids));
+    }
+
     if !self.local_user.map(|l| l.show_nsfw).unwrap_or(false) {
       query = query
         .filter(post::nsfw.eq(false))
@@ -794,6 +799,41 @@ mod tests {
     cleanup(data, conn);
   }
 
+  #[test]
+  #[serial]
+  fn post_listing_language_ids() {
+    let conn = &mut establish_unpooled_connection();
+    let data = init_data(conn);
+
+    let english_id = Language::read_id_from_code(conn, "en").unwrap();
+    let german_id = Language::read_id_from_code(conn, "de").unwrap();
+    for (name, language_id) in [("english post", english_id), ("german post", german_id)] {
+      let post_form = PostForm {
+        name: name.to_string(),
+        creator_id: data.inserted_person.id,
+        community_id: data.inserted_community.id,
+        language_id: Some(language_id),
+        ..PostForm::default()
+      };
+      Post::create(conn, &post_form).unwrap();
+    }
+
+    let post_listing_english = PostQuery::builder()
+      .conn(conn)
+      .sort(Some(SortType::New))
+      .community_id(Some(data.inserted_community.id))
+      .language_ids(Some(vec![english_id]))
+      .build()
+      .list()
+      .unwrap();
+
+    // only the english post should be returned
+    assert_eq!(1, post_listing_english.len());
+    assert_eq!(english_id, post_listing_english[0].post.language_id);
+
+    cleanup(data, conn);
+  }
+
   fn cleanup(data: Data, conn: &mut PgConnection) {
     let num_deleted = Post::delete(conn, data.inserted_post.id).unwrap();
     Community::delete(conn, data.inserted_community.id).unwrap();
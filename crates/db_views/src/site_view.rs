@@ -1,5 +1,5 @@
 use crate::structs::SiteView;
-use diesel::{result::Error, *};
+use diesel::{dsl::InnerJoin, result::Error, *};
 use lemmy_db_schema::{
   aggregates::structs::SiteAggregates,
   schema::{site, site_aggregates},
@@ -8,13 +8,101 @@ use lemmy_db_schema::{
 
 impl SiteView {
   pub fn read_local(conn: &mut PgConnection) -> Result<Self, Error> {
-    let (mut site, counts) = site::table
-      .inner_join(site_aggregates::table)
+    let mut site_view = SiteView::read_local_internal(conn)?;
+    site_view.site.private_key = None;
+    Ok(site_view)
+  }
+
+  /// Same as [`SiteView::read_local`], but returns `None` if the site isn't set up yet.
+  pub fn read_local_opt(conn: &mut PgConnection) -> Result<Option<Self>, Error> {
+    SiteView::read_local(conn).optional()
+  }
+
+  /// Same as [`SiteView::read_local`], but keeps the private key of the site. This is only meant
+  /// for internal code like activity signing, and must never be returned to API clients.
+  pub fn read_local_internal(conn: &mut PgConnection) -> Result<Self, Error> {
+    let (site, counts) = site_join()
       .select((site::all_columns, site_aggregates::all_columns))
       .order_by(site::id)
       .first::<(Site, SiteAggregates)>(conn)?;
 
-    site.private_key = None;
     Ok(SiteView { site, counts })
   }
+
+  /// Reads only the aggregates of the local site.
+  pub fn read_local_counts(conn: &mut PgConnection) -> Result<SiteAggregates, Error> {
+    site_join()
+      .select(site_aggregates::all_columns)
+      .order_by(site::id)
+      .first::<SiteAggregates>(conn)
+  }
+}
+
+/// The join shared by all site reads, so that they all pick the same aggregates row.
+fn site_join() -> InnerJoin<site::table, site_aggregates::table> {
+  site::table.inner_join(site_aggregates::table)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::structs::SiteView;
+  use lemmy_db_schema::{
+    source::site::{Site, SiteForm},
+    traits::Crud,
+    utils::establish_unpooled_connection,
+  };
+  use serial_test::serial;
+
+  #[test]
+  #[serial]
+  fn test_read_local_opt_without_site() {
+    let conn = &mut establish_unpooled_connection();
+
+    assert!(SiteView::read_local_opt(conn).unwrap().is_none());
+  }
+
+  #[test]
+  #[serial]
+  fn test_read_local_private_key() {
+    let conn = &mut establish_unpooled_connection();
+
+    let site_form = SiteForm {
+      name: "test_site_view".into(),
+      public_key: Some("pubkey".to_string()),
+      private_key: Some(Some("privkey".to_string())),
+      ..Default::default()
+    };
+    let inserted_site = Site::create(conn, &site_form).unwrap();
+
+    let site_view = SiteView::read_local(conn).unwrap();
+    let site_view_internal = SiteView::read_local_internal(conn).unwrap();
+
+    Site::delete(conn, inserted_site.id).unwrap();
+
+    assert_eq!(None, site_view.site.private_key);
+    assert_eq!(
+      Some("privkey".to_string()),
+      site_view_internal.site.private_key
+    );
+  }
+
+  #[test]
+  #[serial]
+  fn test_read_local_counts() {
+    let conn = &mut establish_unpooled_connection();
+
+    let site_form = SiteForm {
+      name: "site_view_counts".into(),
+      public_key: Some("pubkey".to_string()),
+      ..Default::default()
+    };
+    let inserted_site = Site::create(conn, &site_form).unwrap();
+
+    let site_view = SiteView::read_local(conn).unwrap();
+    let site_view_counts = SiteView::read_local_counts(conn).unwrap();
+
+    Site::delete(conn, inserted_site.id).unwrap();
+
+    assert_eq!(site_view.counts, site_view_counts);
+  }
 }
@@ -0,0 +1 @@
+alter table language drop column deprecated;
@@ -0,0 +1 @@
+alter table language add column deprecated boolean default false not null;